@@ -0,0 +1,238 @@
+use crate::error::KrakenError;
+use chrono::DateTime;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub trait Validatable {
+    fn check_valid(&self) -> Result<(), KrakenError>;
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeResult {
+    pub unixtime: i64,
+    pub rfc1123: String,
+}
+
+impl Validatable for TimeResult {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        // rfc2822 is a newer format of rfc1233, thus they should be compatible
+        let time_rfc2822 = DateTime::parse_from_rfc2822(&self.rfc1123)
+            .map_err(|e| KrakenError::Validation(format!("can't parse rfc1233 time: {}", e)))?;
+        // Expect that unixtime is the same time as the rfc1233 field
+        if time_rfc2822.timestamp() != self.unixtime {
+            return Err(KrakenError::Validation(format!(
+                "unixtime {} does not match rfc1123 time {}",
+                self.unixtime, self.rfc1123
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TickerResultData {
+    #[serde(rename(deserialize = "a"))]
+    ask: [String; 3],
+
+    #[serde(rename(deserialize = "b"))]
+    bid: [String; 3],
+
+    #[serde(rename(deserialize = "c"))]
+    closed: [String; 2],
+
+    #[serde(rename(deserialize = "v"))]
+    volume: [String; 2],
+
+    #[serde(rename(deserialize = "p"))]
+    weighted_average_volume: [String; 2],
+
+    #[serde(rename(deserialize = "t"))]
+    number_of_trades: [u64; 2],
+
+    #[serde(rename(deserialize = "l"))]
+    low: [String; 2],
+    #[serde(rename(deserialize = "h"))]
+    high: [String; 2],
+    #[serde(rename(deserialize = "o"))]
+    day_opening_price: String,
+}
+
+// Kraken returns prices/volumes as precise decimal strings; parsing them as
+// f64 would let float rounding make the ask > bid and positivity checks
+// below flaky.
+fn as_decimal_array(arr: &[String]) -> Result<Vec<Decimal>, KrakenError> {
+    arr.iter()
+        .map(|val| {
+            Decimal::from_str(val).map_err(|e| {
+                KrakenError::Validation(format!("can't parse '{}' as a decimal: {}", val, e))
+            })
+        })
+        .collect()
+}
+
+fn require(condition: bool, message: impl Into<String>) -> Result<(), KrakenError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(KrakenError::Validation(message.into()))
+    }
+}
+
+impl Validatable for TickerResultData {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        require(
+            self.number_of_trades[0] != 0,
+            "today's number of trades is 0",
+        )?;
+        require(
+            self.number_of_trades[1] != 0,
+            "last 24h number of trades is 0",
+        )?;
+        require(
+            self.number_of_trades[0] < self.number_of_trades[1],
+            "today's number of trades is not less than last 24h",
+        )?;
+        let asks = as_decimal_array(self.ask.as_ref())?;
+        require(
+            asks.iter().all(|&v| v > Decimal::ZERO),
+            "ask price is not positive",
+        )?;
+
+        let bids = as_decimal_array(self.bid.as_ref())?;
+        require(
+            bids.iter().all(|&v| v > Decimal::ZERO),
+            "bid price is not positive",
+        )?;
+        // The best ask must be strictly above the best bid, otherwise the
+        // order book has crossed.
+        require(
+            asks[0] > bids[0],
+            format!("best ask {} is not above best bid {}", asks[0], bids[0]),
+        )?;
+
+        let closed = as_decimal_array(self.closed.as_ref())?;
+        //maybe this fails at beginning of the day
+        require(
+            closed.iter().all(|&v| v > Decimal::ZERO),
+            "closed price is not positive",
+        )?;
+
+        let volume = as_decimal_array(self.volume.as_ref())?;
+        // since we only test with XBT, the volume for last 24 hours can't be null
+        // at beginning of the day this can be null
+        require(
+            volume[1..].iter().all(|&v| v > Decimal::ZERO),
+            "last 24h volume is not positive",
+        )?;
+
+        let wav = as_decimal_array(self.weighted_average_volume.as_ref())?;
+        // since we only test with XBT, the volume for last 24 hours can't be null
+        // at beginning of the day this can be null
+        require(
+            wav[1..].iter().all(|&v| v > Decimal::ZERO),
+            "last 24h weighted average volume is not positive",
+        )?;
+
+        let low = as_decimal_array(self.low.as_ref())?;
+        require(
+            low.iter().all(|&v| v > Decimal::ZERO),
+            "low price is not positive",
+        )?;
+
+        let high = as_decimal_array(self.high.as_ref())?;
+        require(
+            high.iter().all(|&v| v > Decimal::ZERO),
+            "high price is not positive",
+        )?;
+
+        let open = as_decimal_array(&[self.day_opening_price.clone()][..])?;
+        require(
+            open.iter().all(|&v| v > Decimal::ZERO),
+            "opening price is not positive",
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TickerResult(HashMap<String, TickerResultData>);
+
+impl TickerResult {
+    pub fn pairs(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+
+    pub fn print_price(&self, pair: &str) -> Result<(), KrakenError> {
+        let data = self
+            .0
+            .get(pair)
+            .ok_or_else(|| KrakenError::Validation(format!("no ticker data for {}", pair)))?;
+        println!("{} last price: {}", pair, data.closed[0]);
+        Ok(())
+    }
+}
+
+impl Validatable for TickerResult {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        require(!self.0.is_empty(), "ticker result has no pairs")?;
+        for data in self.0.values() {
+            data.check_valid()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrdersResult {
+    pub open: serde_json::Value,
+}
+
+impl Validatable for OrdersResult {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        require(
+            self.open.is_object(),
+            format!("'open' is not a JSON object: {}", self.open),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WebSocketsTokenResult {
+    pub token: String,
+}
+
+impl Validatable for WebSocketsTokenResult {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        require(!self.token.is_empty(), "websockets token is empty")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Answer<T> {
+    pub error: Vec<serde_json::Value>,
+    // Option because if answer fails, the result is not present
+    pub result: Option<T>,
+}
+
+impl<T: Validatable> Validatable for Answer<T> {
+    fn check_valid(&self) -> Result<(), KrakenError> {
+        if !self.error.is_empty() {
+            let messages = self
+                .error
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| v.to_string())
+                })
+                .collect();
+            return Err(KrakenError::ApiError(messages));
+        }
+        self.result
+            .as_ref()
+            .ok_or_else(|| KrakenError::Validation("Answer has no result".into()))?
+            .check_valid()
+    }
+}
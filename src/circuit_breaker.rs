@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const FAILURE_THRESHOLD: u32 = 5;
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+enum State {
+    // Requests are allowed through; counts consecutive failures so far.
+    Closed { consecutive_failures: u32 },
+    // Short-circuiting requests until the cooldown window elapses.
+    Open { opened_at: Instant },
+    // Cooldown elapsed; a single probe request is allowed through.
+    HalfOpen,
+}
+
+struct Breaker {
+    state: State,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: State::Closed {
+                consecutive_failures: 0,
+            },
+        }
+    }
+
+    fn should_try(&mut self) -> bool {
+        match self.state {
+            State::Closed { .. } => true,
+            // The probe was already let through on the Open -> HalfOpen
+            // transition; hold everything else until its outcome is recorded.
+            State::HalfOpen => false,
+            State::Open { opened_at } => {
+                if opened_at.elapsed() >= COOLDOWN {
+                    self.state = State::HalfOpen;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.state = State::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    fn record_failure(&mut self) {
+        self.state = match self.state {
+            State::Closed {
+                consecutive_failures,
+            } if consecutive_failures + 1 >= FAILURE_THRESHOLD => State::Open {
+                opened_at: Instant::now(),
+            },
+            State::Closed {
+                consecutive_failures,
+            } => State::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            State::HalfOpen => State::Open {
+                opened_at: Instant::now(),
+            },
+            State::Open { opened_at } => State::Open { opened_at },
+        };
+    }
+}
+
+thread_local! {
+    // cucumber_rust rebuilds `KrakenClient` (and thus a fresh `Breakers`)
+    // once per scenario, so per-client state never accumulates the
+    // consecutive failures needed to trip. Sharing one instance across
+    // scenarios, the same way `NonceManager`'s state outlives a single
+    // client, lets an outage actually open the breaker for the rest of the
+    // run instead of every scenario paying the full retry budget.
+    static SHARED: Rc<RefCell<Breakers>> = Rc::new(RefCell::new(Breakers::default()));
+}
+
+/// Per-host circuit breakers, so a prolonged outage on one Kraken host makes
+/// scenarios fail fast instead of burning the full retry budget on every step.
+#[derive(Default)]
+pub struct Breakers {
+    by_host: HashMap<String, Breaker>,
+}
+
+impl Breakers {
+    /// Returns the breaker state shared by every `KrakenClient` built on
+    /// this thread, so it persists across the per-scenario `MyWorld::new()`
+    /// cucumber_rust calls rather than resetting each time.
+    pub fn shared() -> Rc<RefCell<Breakers>> {
+        SHARED.with(|breakers| breakers.clone())
+    }
+
+    pub fn should_try(&mut self, host: &str) -> bool {
+        self.by_host
+            .entry(host.to_string())
+            .or_insert_with(Breaker::new)
+            .should_try()
+    }
+
+    pub fn record_success(&mut self, host: &str) {
+        if let Some(breaker) = self.by_host.get_mut(host) {
+            breaker.record_success();
+        }
+    }
+
+    pub fn record_failure(&mut self, host: &str) {
+        self.by_host
+            .entry(host.to_string())
+            .or_insert_with(Breaker::new)
+            .record_failure();
+    }
+}
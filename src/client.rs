@@ -0,0 +1,235 @@
+use crate::answer::{Answer, OrdersResult, TickerResult, TimeResult, WebSocketsTokenResult};
+use crate::circuit_breaker::Breakers;
+use crate::error::KrakenError;
+use crate::nonce::NonceManager;
+use crate::retry;
+pub use crate::retry::ApiResponse;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256, Sha512};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const API_HOST: &str = "api.kraken.com";
+const API_DOMAIN: &str = "https://api.kraken.com";
+const NONCE_STATE_FILE: &str = ".kraken-nonce-state.json";
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Where the one-time-password value that goes on every private request
+/// comes from. Kraken's two-factor setup can hand either a static OTP string
+/// or a TOTP secret that must be turned into a fresh code per request.
+#[derive(Clone)]
+pub enum OtpSource {
+    None,
+    Static(String),
+    TotpSecret(String),
+}
+
+impl OtpSource {
+    fn code(&self) -> Result<Option<String>, KrakenError> {
+        match self {
+            OtpSource::None => Ok(None),
+            OtpSource::Static(otp) => Ok(Some(otp.clone())),
+            OtpSource::TotpSecret(secret) => {
+                let start_code =
+                    base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)
+                        .ok_or_else(|| KrakenError::Signing("OTP secret is not valid base32".into()))?;
+                let otp_code = oath::totp_raw_now(&start_code, 6, 0, 30, &oath::HashType::SHA1);
+                Ok(Some(otp_code.to_string()))
+            }
+        }
+    }
+}
+
+fn build_public_request(http: &Client, endpoint: &str) -> Result<RequestBuilder, KrakenError> {
+    let url = format!("{}{}", API_DOMAIN, endpoint);
+    Ok(http.get(url).header("User-Agent", "Kraken REST API"))
+}
+
+// Called once per retry attempt: the nonce and HMAC signature it embeds are
+// only valid for a single send, so a retry has to rebuild the request rather
+// than resend the one that just failed.
+fn build_private_request(
+    http: &Client,
+    public_key: &str,
+    private_key: &str,
+    otp: &OtpSource,
+    nonces: &Rc<RefCell<NonceManager>>,
+    endpoint: &str,
+    extra_params: &[(String, String)],
+) -> Result<RequestBuilder, KrakenError> {
+    let nonce: u64 = nonces.borrow_mut().next(public_key);
+    let nonce_str = nonce.to_string();
+    let mut post_data: Vec<(&str, &str)> = vec![("nonce", nonce_str.as_str())];
+    let otp_code = otp.code()?;
+    if let Some(otp_code) = otp_code.as_deref() {
+        post_data.push(("otp", otp_code));
+    }
+    for (key, value) in extra_params {
+        post_data.push((key.as_str(), value.as_str()));
+    }
+
+    let url = format!("{}{}", API_DOMAIN, endpoint);
+    let encoded = serde_urlencoded::to_string(&post_data)
+        .map_err(|e| KrakenError::Signing(format!("can't encode post_data: {}", e)))?;
+    let to_hash = format!("{}{}", nonce, encoded);
+
+    let sha256_digest = Sha256::digest(to_hash.as_bytes());
+    let api_secret = base64::decode(private_key)
+        .map_err(|e| KrakenError::Signing(format!("can't decode private key: {}", e)))?;
+    let mut mac = HmacSha512::new_varkey(&api_secret)
+        .map_err(|e| KrakenError::Signing(format!("can't create hmac: {}", e)))?;
+    mac.update(endpoint.as_bytes());
+    mac.update(&sha256_digest);
+    let signature = mac.finalize();
+
+    Ok(http
+        .post(url)
+        .form(&post_data)
+        .header("API-Key", public_key)
+        .header("API-Sign", base64::encode(signature.into_bytes()))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("User-Agent", "Kraken REST API"))
+}
+
+/// A thin, signing-aware Kraken REST client.
+///
+/// Every call retries transient failures with backoff and consults a
+/// per-host circuit breaker, so callers never deal with nonces, HMAC
+/// signatures or rate limits directly — that's the whole point of this type:
+/// it used to be copy-pasted into every cucumber step that needed to talk to
+/// a private endpoint.
+pub struct KrakenClient {
+    http: Client,
+    public_key: String,
+    private_key: String,
+    otp: OtpSource,
+    breakers: Rc<RefCell<Breakers>>,
+    nonces: Rc<RefCell<NonceManager>>,
+}
+
+impl KrakenClient {
+    /// Builds a client that persists its nonce sequence to
+    /// `.kraken-nonce-state.json` in the current directory, so nonces stay
+    /// strictly increasing even across separate test runs.
+    pub fn new(public_key: String, private_key: String, otp: OtpSource) -> Result<Self, KrakenError> {
+        Self::with_nonce_state_path(public_key, private_key, otp, NONCE_STATE_FILE)
+    }
+
+    pub fn with_nonce_state_path(
+        public_key: String,
+        private_key: String,
+        otp: OtpSource,
+        nonce_state_path: impl Into<std::path::PathBuf>,
+    ) -> Result<Self, KrakenError> {
+        Ok(Self {
+            http: Client::new(),
+            public_key,
+            private_key,
+            otp,
+            breakers: Breakers::shared(),
+            nonces: Rc::new(RefCell::new(NonceManager::load(nonce_state_path)?)),
+        })
+    }
+
+    async fn send(
+        &mut self,
+        build: impl Fn() -> Result<RequestBuilder, KrakenError>,
+    ) -> Result<ApiResponse, KrakenError> {
+        if !self.breakers.borrow_mut().should_try(API_HOST) {
+            return Err(KrakenError::BreakerOpen(API_HOST.to_string()));
+        }
+        let result = retry::send_with_retry(build).await;
+        match &result {
+            Ok(_) => self.breakers.borrow_mut().record_success(API_HOST),
+            Err(_) => self.breakers.borrow_mut().record_failure(API_HOST),
+        }
+        result
+    }
+
+    /// Calls a public endpoint and returns the raw, buffered response body,
+    /// for callers that need to decide how to decode it themselves.
+    pub async fn get_raw(&mut self, endpoint: &str) -> Result<ApiResponse, KrakenError> {
+        let http = self.http.clone();
+        let endpoint = endpoint.to_string();
+        self.send(move || build_public_request(&http, &endpoint)).await
+    }
+
+    /// Calls a private endpoint and returns the raw, buffered response body.
+    pub async fn post_private_raw(
+        &mut self,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> Result<ApiResponse, KrakenError> {
+        let http = self.http.clone();
+        let endpoint = endpoint.to_string();
+        let public_key = self.public_key.clone();
+        let private_key = self.private_key.clone();
+        let otp = self.otp.clone();
+        let nonces = self.nonces.clone();
+        let params: Vec<(String, String)> = params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self.send(move || {
+            build_private_request(
+                &http,
+                &public_key,
+                &private_key,
+                &otp,
+                &nonces,
+                &endpoint,
+                &params,
+            )
+        })
+        .await
+    }
+
+    /// Calls a public endpoint and decodes the JSON answer as `T`.
+    pub async fn get<T: DeserializeOwned>(&mut self, endpoint: &str) -> Result<Answer<T>, KrakenError> {
+        let response = self.get_raw(endpoint).await?;
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// Calls a private endpoint and decodes the JSON answer as `T`.
+    pub async fn post_private<T: DeserializeOwned>(
+        &mut self,
+        endpoint: &str,
+        params: &[(&str, &str)],
+    ) -> Result<Answer<T>, KrakenError> {
+        let response = self.post_private_raw(endpoint, params).await?;
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// Generic escape hatch for endpoints that don't have a dedicated method
+    /// below: `params` of `None` calls a public endpoint, `Some` a private one.
+    pub async fn call<T: DeserializeOwned>(
+        &mut self,
+        endpoint: &str,
+        params: Option<&[(&str, &str)]>,
+    ) -> Result<Answer<T>, KrakenError> {
+        match params {
+            Some(params) => self.post_private(endpoint, params).await,
+            None => self.get(endpoint).await,
+        }
+    }
+
+    pub async fn server_time(&mut self) -> Result<Answer<TimeResult>, KrakenError> {
+        self.get("/0/public/Time").await
+    }
+
+    pub async fn ticker(&mut self, pairs: &[String]) -> Result<Answer<TickerResult>, KrakenError> {
+        let endpoint = format!("/0/public/Ticker?pair={}", pairs.join(","));
+        self.get(&endpoint).await
+    }
+
+    pub async fn open_orders(&mut self) -> Result<Answer<OrdersResult>, KrakenError> {
+        self.post_private("/0/private/OpenOrders", &[]).await
+    }
+
+    pub async fn websockets_token(&mut self) -> Result<Answer<WebSocketsTokenResult>, KrakenError> {
+        self.post_private("/0/private/GetWebSocketsToken", &[]).await
+    }
+}
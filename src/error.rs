@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+/// The single error type surfaced by every step in this harness.
+///
+/// Replaces the scattered `.expect()`/`panic!()` calls that used to abort a
+/// scenario with an opaque backtrace instead of the actual Kraken response.
+#[derive(Debug, Error)]
+pub enum KrakenError {
+    #[error("request to Kraken failed: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    #[error("failed to sign request: {0}")]
+    Signing(String),
+
+    #[error("failed to decode Kraken response: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Kraken API returned an error: {0:?}")]
+    ApiError(Vec<String>),
+
+    #[error("validation failed: {0}")]
+    Validation(String),
+
+    #[error("circuit breaker for {0} is open, failing fast instead of retrying")]
+    BreakerOpen(String),
+
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+}
@@ -0,0 +1,15 @@
+//! A small Kraken REST/WebSocket client, factored out of the cucumber test
+//! harness in `tests/` so that request signing, retrying and circuit
+//! breaking only have to be implemented once.
+
+mod circuit_breaker;
+mod nonce;
+mod retry;
+
+pub mod answer;
+pub mod client;
+pub mod error;
+pub mod ws;
+
+pub use client::{ApiResponse, KrakenClient, OtpSource};
+pub use error::KrakenError;
@@ -0,0 +1,90 @@
+use crate::error::KrakenError;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Guarantees a strictly increasing nonce per API key.
+///
+/// Kraken rejects a request whose nonce is not strictly greater than the
+/// previous one it saw for that key, so deriving the nonce from the current
+/// timestamp alone breaks under clock adjustments or two scenarios landing
+/// in the same millisecond. The last nonce handed out per key is persisted
+/// to a small JSON state file so the guarantee survives across test runs,
+/// not just within one.
+pub struct NonceManager {
+    path: PathBuf,
+    last_by_key: HashMap<String, u64>,
+    dirty: bool,
+}
+
+impl NonceManager {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, KrakenError> {
+        let path = path.into();
+        let last_by_key = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| {
+                KrakenError::Validation(format!(
+                    "can't parse nonce state file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(KrakenError::Validation(format!(
+                    "can't read nonce state file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            path,
+            last_by_key,
+            dirty: false,
+        })
+    }
+
+    /// Returns the next nonce for `api_key`: `max(persisted + 1, now_ms)`.
+    pub fn next(&mut self, api_key: &str) -> u64 {
+        let now_ms = Utc::now().timestamp_millis() as u64;
+        let next = match self.last_by_key.get(api_key) {
+            Some(&last) => last.saturating_add(1).max(now_ms),
+            None => now_ms,
+        };
+        self.last_by_key.insert(api_key.to_string(), next);
+        self.dirty = true;
+        next
+    }
+
+    fn flush(&mut self) -> Result<(), KrakenError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let contents = serde_json::to_string(&self.last_by_key)
+            .map_err(|e| KrakenError::Validation(format!("can't encode nonce state: {}", e)))?;
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| {
+                KrakenError::Validation(format!("can't create {}: {}", parent.display(), e))
+            })?;
+        }
+        fs::write(&self.path, contents).map_err(|e| {
+            KrakenError::Validation(format!(
+                "can't write nonce state file {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for NonceManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("failed to persist nonce state: {}", e);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::KrakenError;
+use bytes::Bytes;
+use rand::Rng;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// A response (status and body) that has already had its body buffered, so callers
+/// can inspect it (and retry on it) without fighting reqwest's streaming API.
+pub struct ApiResponse {
+    pub status: StatusCode,
+    pub body: Bytes,
+}
+
+const MAX_ATTEMPTS: u32 = 5;
+const BASE_DELAY: Duration = Duration::from_millis(200);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct ErrorPeek {
+    #[serde(default)]
+    error: Vec<serde_json::Value>,
+}
+
+fn mentions_rate_limit(body: &Bytes) -> bool {
+    serde_json::from_slice::<ErrorPeek>(body)
+        .map(|peek| {
+            peek.error.iter().any(|e| {
+                e.as_str()
+                    .map(|s| s.to_lowercase().contains("rate limit"))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false)
+}
+
+fn is_retryable(status: StatusCode, body: &Bytes) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() || mentions_rate_limit(body)
+}
+
+// Exponential backoff with full jitter: a random delay in [0, base * 2^attempt],
+// capped at MAX_DELAY.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exp_millis = (BASE_DELAY.as_millis() as u64).saturating_mul(1u64 << attempt.min(20));
+    let capped_millis = exp_millis.min(MAX_DELAY.as_millis() as u64);
+    let jittered_millis = rand::thread_rng().gen_range(0..=capped_millis.max(1));
+    Duration::from_millis(jittered_millis)
+}
+
+/// Sends a request, retrying with exponential backoff and full jitter on
+/// transport errors, HTTP 5xx/429, or an `error` array mentioning a rate limit.
+///
+/// `build_request` is called again for every attempt instead of reusing a
+/// finalized `RequestBuilder`: a signed request embeds a nonce in its HMAC, so
+/// a retry has to rebuild the request (fresh nonce, fresh signature) rather
+/// than resend the one that just failed.
+pub async fn send_with_retry<F>(mut build_request: F) -> Result<ApiResponse, KrakenError>
+where
+    F: FnMut() -> Result<RequestBuilder, KrakenError>,
+{
+    let mut last_err = None;
+    for attempt in 0..MAX_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(backoff_delay(attempt - 1)).await;
+        }
+        let req = build_request()?;
+        match req.send().await {
+            Ok(res) => {
+                let status = res.status();
+                let body = match res.bytes().await {
+                    Ok(body) => body,
+                    Err(e) => {
+                        last_err = Some(KrakenError::Transport(e));
+                        continue;
+                    }
+                };
+                if is_retryable(status, &body) && attempt + 1 < MAX_ATTEMPTS {
+                    last_err = Some(KrakenError::Validation(format!(
+                        "retryable response (status {}) on attempt {} of {}",
+                        status,
+                        attempt + 1,
+                        MAX_ATTEMPTS
+                    )));
+                    continue;
+                }
+                return Ok(ApiResponse { status, body });
+            }
+            Err(e) => last_err = Some(KrakenError::Transport(e)),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| KrakenError::Validation("retry budget exhausted".into())))
+}
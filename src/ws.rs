@@ -0,0 +1,74 @@
+use crate::error::KrakenError;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+pub const PUBLIC_WS_URL: &str = "wss://ws.kraken.com";
+pub const PRIVATE_WS_URL: &str = "wss://ws-auth.kraken.com";
+
+// How long to wait for a single message before giving up on the channel
+// rather than hanging forever on a stale subscription.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn ticker_subscribe_frame(pairs: &[String]) -> Value {
+    json!({
+        "event": "subscribe",
+        "subscription": { "name": "ticker" },
+        "pair": pairs,
+    })
+}
+
+pub fn open_orders_subscribe_frame(token: &str) -> Value {
+    json!({
+        "event": "subscribe",
+        "subscription": { "name": "openOrders", "token": token },
+    })
+}
+
+/// Opens a websocket to `url`, sends `subscribe_frame`, and collects `count`
+/// channel-data messages.
+///
+/// Kraken interleaves a JSON-object subscription-status ack with the
+/// JSON-array channel updates on the same socket, so acks are skipped rather
+/// than counted.
+pub async fn collect_channel_messages(
+    url: &str,
+    subscribe_frame: Value,
+    count: usize,
+) -> Result<Vec<Value>, KrakenError> {
+    let (mut stream, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(|e| KrakenError::Validation(format!("websocket connect to {} failed: {}", url, e)))?;
+
+    stream
+        .send(Message::Text(subscribe_frame.to_string()))
+        .await
+        .map_err(|e| KrakenError::Validation(format!("can't send subscribe frame: {}", e)))?;
+
+    let mut messages = Vec::with_capacity(count);
+    while messages.len() < count {
+        let message = tokio::time::timeout(MESSAGE_TIMEOUT, stream.next())
+            .await
+            .map_err(|_| {
+                KrakenError::Timeout(format!(
+                    "waiting for a channel update ({}/{} received)",
+                    messages.len(),
+                    count
+                ))
+            })?
+            .ok_or_else(|| {
+                KrakenError::Validation("websocket closed before enough messages arrived".into())
+            })?
+            .map_err(|e| KrakenError::Validation(format!("websocket read failed: {}", e)))?;
+        let text = match message {
+            Message::Text(text) => text,
+            _ => continue,
+        };
+        let value: Value = serde_json::from_str(&text)?;
+        if value.is_array() {
+            messages.push(value);
+        }
+    }
+    Ok(messages)
+}
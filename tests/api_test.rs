@@ -1,140 +1,225 @@
 use cucumber_rust::{async_trait, given, then, when, World, WorldInit};
-use reqwest::{Client, RequestBuilder, Response};
+use open_orders::answer::{self, Validatable};
+use open_orders::{ApiResponse, KrakenClient, KrakenError, OtpSource};
 use serde_json;
-use std::convert::Infallible;
 
-mod answer_data;
-const API_DOMAIN: &str = "https://api.kraken.com";
+enum PendingRequest {
+    Public { path: String },
+    Private { path: String },
+}
+
+/// A decoded answer from one of `KrakenClient`'s typed endpoint methods.
+enum TypedResponse {
+    Time(answer::Answer<answer::TimeResult>),
+    Ticker(answer::Answer<answer::TickerResult>),
+    Orders(answer::Answer<answer::OrdersResult>),
+}
 
-pub trait Validatable {
-    fn check_valid(&self);
+/// What `i_request` got back: a known endpoint goes through a typed
+/// `KrakenClient` method, anything else falls back to the raw response so
+/// status-only scenarios still work against arbitrary urls.
+enum StepResponse {
+    Typed(TypedResponse),
+    Raw(ApiResponse),
 }
 
 #[derive(WorldInit)]
 pub struct MyWorld {
-    request_builder: Option<RequestBuilder>,
-    response: Option<Response>,
-    api_public_key: String,
-    api_private_key: String,
-    two_factor_pwd: String,
+    client: KrakenClient,
+    pending_request: Option<PendingRequest>,
+    response: Option<StepResponse>,
 }
 
 #[async_trait(?Send)]
 impl World for MyWorld {
-    type Error = Infallible;
+    type Error = KrakenError;
 
-    async fn new() -> Result<Self, Infallible> {
+    async fn new() -> Result<Self, KrakenError> {
         use std::env;
+        fn required_env(name: &str) -> Result<String, KrakenError> {
+            env::var(name)
+                .map_err(|_| KrakenError::Validation(format!("missing environment variable {}", name)))
+        }
+        let otp = OtpSource::Static(required_env("OTP")?);
         Ok(Self {
-            request_builder: None,
+            client: KrakenClient::new(
+                required_env("API_Public_Key")?,
+                required_env("API_Private_Key")?,
+                otp,
+            )?,
+            pending_request: None,
             response: None,
-            api_public_key: env::var("API_Public_Key")
-                .expect("to have the environment variable API_Public_Key"),
-            api_private_key: env::var("API_Private_Key")
-                .expect("to have the environment variable API_Private_Key"),
-            two_factor_pwd: env::var("OTP").expect("to have the environment variable OTP"),
         })
     }
 }
 
 #[given(regex = "A request to public url (.*)")]
 fn public_api(world: &mut MyWorld, url: String) {
-    let request_url = format!("{}{}", API_DOMAIN, url);
-    let req_builder = Client::new()
-        .get(request_url)
-        .header("User-Agent", "Kraken REST API");
-    world.request_builder = Some(req_builder);
+    world.pending_request = Some(PendingRequest::Public { path: url });
 }
 
 #[given(regex = "An authenticated request to private url (.*)")]
 fn private_api(world: &mut MyWorld, url: String) {
-    let nonce: u64 = chrono::offset::Utc::now().timestamp_millis() as u64;
-    //let nonce: u64 = 1618690640656;
-    let request_url = format!("{}{}", API_DOMAIN, url);
-    let post_data = format!("&nonce={}&otp={}", nonce, world.two_factor_pwd);
-    let to_hash = format!("{}{}", nonce, post_data);
-
-    use sha2::{Digest, Sha256, Sha512};
-    let sha256_digest = Sha256::digest(to_hash.as_bytes());
-
-    use hmac::{Hmac, Mac, NewMac};
-    type HmacSha512 = Hmac<Sha512>;
-    let api_secret = base64::decode(world.api_private_key.as_str()).expect("to decode private key");
-    let mut mac = HmacSha512::new_varkey(&api_secret).expect("to be able to create hmac");
-    mac.update(&url.as_bytes());
-    mac.update(&sha256_digest);
-    let hmac_sha512 = mac.finalize();
-
-    let req_builder = Client::new()
-        .post(request_url)
-        .body(post_data)
-        .header("API-Key", world.api_public_key.clone())
-        .header("API-Sign", base64::encode(hmac_sha512.into_bytes()))
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .header("User-Agent", "Kraken REST API");
-    world.request_builder.replace(req_builder);
+    world.pending_request = Some(PendingRequest::Private { path: url });
+}
+
+// cucumber_rust 0.9's codegen discards whatever a step function returns
+// (`#func_name(...).await;`), and its runner only fails a step via
+// `panic::catch_unwind`. So a step that wants a failing scenario has to
+// panic at its own boundary rather than return an `Err` — each `#[given]`/
+// `#[when]`/`#[then]` below keeps its `Result`-returning logic in an `_impl`
+// helper and unwraps it here.
+fn expect_step(result: Result<(), KrakenError>) {
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
 }
 
 #[when("I send it")]
 async fn i_request(world: &mut MyWorld) {
-    let req = world
-        .request_builder
+    expect_step(i_request_impl(world).await);
+}
+
+async fn i_request_impl(world: &mut MyWorld) -> Result<(), KrakenError> {
+    let pending = world
+        .pending_request
         .take()
-        .expect("to have a request already built");
-    let res = req.send().await;
-    if !res.is_ok() {
-        println!("{:?}", res);
-        panic!("Server responded with error")
+        .ok_or_else(|| KrakenError::Validation("no request has been built yet".into()))?;
+    world.response = Some(fetch(&mut world.client, pending).await?);
+    Ok(())
+}
+
+/// Routes known endpoints through `KrakenClient`'s typed methods, so the
+/// steps are thin wrappers around them rather than hand-building the
+/// request/decode logic; anything else falls back to a raw call.
+async fn fetch(client: &mut KrakenClient, pending: PendingRequest) -> Result<StepResponse, KrakenError> {
+    match pending {
+        PendingRequest::Public { path } if path == "/0/public/Time" => Ok(StepResponse::Typed(
+            TypedResponse::Time(client.server_time().await?),
+        )),
+        PendingRequest::Public { path } => match ticker_pairs(&path) {
+            Some(pairs) => Ok(StepResponse::Typed(TypedResponse::Ticker(
+                client.ticker(&pairs).await?,
+            ))),
+            None => Ok(StepResponse::Raw(client.get_raw(&path).await?)),
+        },
+        PendingRequest::Private { path } if path == "/0/private/OpenOrders" => Ok(
+            StepResponse::Typed(TypedResponse::Orders(client.open_orders().await?)),
+        ),
+        PendingRequest::Private { path } => {
+            Ok(StepResponse::Raw(client.post_private_raw(&path, &[]).await?))
+        }
     }
-    world.response = res.ok();
+}
+
+/// Extracts the comma-separated `pair` query parameter from a `/0/public/
+/// Ticker?pair=...` path, the same way `ticker_subscription` in
+/// `tests/api_tests.rs` splits the websocket channel's pair list.
+fn ticker_pairs(path: &str) -> Option<Vec<String>> {
+    let query = path.strip_prefix("/0/public/Ticker?")?;
+    let pairs = query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("pair="))?
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .collect();
+    Some(pairs)
 }
 
 #[then(regex = "The server responds with status (.*)")]
 fn server_responds(world: &mut MyWorld, status: String) {
+    expect_step(server_responds_impl(world, status));
+}
+
+fn server_responds_impl(world: &mut MyWorld, status: String) -> Result<(), KrakenError> {
     match status.to_lowercase().as_str() {
-        "ok" => {
-            let status = world.response.as_ref().map(|r| r.status().is_success());
-            if status != Some(true) {
-                println!("{:?}", world.response);
+        "ok" => match world.response.as_ref() {
+            // A typed call already decoded successfully to get here.
+            Some(StepResponse::Typed(_)) => Ok(()),
+            Some(StepResponse::Raw(response)) if response.status.is_success() => Ok(()),
+            other => {
+                let status = match other {
+                    Some(StepResponse::Raw(response)) => Some(response.status),
+                    _ => None,
+                };
+                Err(KrakenError::Validation(format!(
+                    "expected a successful response, got {:?}",
+                    status
+                )))
             }
-            assert_eq!(status, Some(true));
-        }
-        _ => panic!("not implemented"),
+        },
+        _ => Err(KrakenError::Validation(format!(
+            "unrecognized status check: {}",
+            status
+        ))),
     }
 }
 
 #[then(regex = "The response has the correct (time|ticker|orders) format")]
-async fn response_time_format(world: &mut MyWorld, check_type: String) {
-    let response = world.response.take().expect("to have a response");
-    let resp_bytes = response
-        .bytes()
-        .await
-        .expect("to have been able to read response");
-    match check_type.to_lowercase().as_str() {
-        "time" => {
-            //json response validation
-            let resp_json: answer_data::Answer<answer_data::TimeResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            println!("Server responded with time: {}", resp_json.result.rfc1123);
-            resp_json.result.check_valid();
+fn response_time_format(world: &mut MyWorld, check_type: String) {
+    expect_step(response_time_format_impl(world, check_type));
+}
+
+fn response_time_format_impl(world: &mut MyWorld, check_type: String) -> Result<(), KrakenError> {
+    let response = world
+        .response
+        .take()
+        .ok_or_else(|| KrakenError::Validation("no response has been received yet".into()))?;
+    match (check_type.to_lowercase().as_str(), response) {
+        ("time", StepResponse::Typed(TypedResponse::Time(resp_json))) => {
+            resp_json.check_valid()?;
+            println!(
+                "Server responded with time: {}",
+                resp_json.result.unwrap().rfc1123
+            );
         }
-        "ticker" => {
-            //json response validation
-            let resp_json: answer_data::Answer<answer_data::TickerResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            resp_json.result.check_valid();
-            resp_json.result.print_price();
+        ("time", StepResponse::Raw(response)) => {
+            let resp_json: answer::Answer<answer::TimeResult> =
+                serde_json::from_slice(&response.body)?;
+            resp_json.check_valid()?;
+            println!(
+                "Server responded with time: {}",
+                resp_json.result.unwrap().rfc1123
+            );
         }
-        "orders" => {
-            let resp_json: answer_data::Answer<answer_data::OrdersResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            resp_json.result.check_valid();
-            let order_names: Vec<&String> =
-                resp_json.result.open.as_object().unwrap().keys().collect();
+        ("ticker", StepResponse::Typed(TypedResponse::Ticker(resp_json))) => {
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
+        }
+        ("ticker", StepResponse::Raw(response)) => {
+            let resp_json: answer::Answer<answer::TickerResult> =
+                serde_json::from_slice(&response.body)?;
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
+        }
+        ("orders", StepResponse::Typed(TypedResponse::Orders(resp_json))) => {
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            let order_names: Vec<&String> = result.open.as_object().unwrap().keys().collect();
             println!("Got {} open orders: {:?}", order_names.len(), order_names);
         }
-        _ => panic!("unrecognized check type"),
+        ("orders", StepResponse::Raw(response)) => {
+            let resp_json: answer::Answer<answer::OrdersResult> =
+                serde_json::from_slice(&response.body)?;
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            let order_names: Vec<&String> = result.open.as_object().unwrap().keys().collect();
+            println!("Got {} open orders: {:?}", order_names.len(), order_names);
+        }
+        (other, _) => {
+            return Err(KrakenError::Validation(format!(
+                "unrecognized check type: {}",
+                other
+            )))
+        }
     }
+    Ok(())
 }
 
 #[tokio::main]
@@ -1,157 +1,303 @@
-use chrono::offset::Utc;
 use cucumber_rust::{async_trait, given, then, when, World, WorldInit};
-use reqwest::{Client, RequestBuilder, Response};
+use open_orders::answer::{self, Validatable};
+use open_orders::{ws, ApiResponse, KrakenClient, KrakenError, OtpSource};
 use serde_json;
-use std::convert::Infallible;
 
-mod answer_data;
-const API_DOMAIN: &str = "https://api.kraken.com";
+enum PendingRequest {
+    Public { path: String },
+    Private { path: String },
+}
+
+/// A decoded answer from one of `KrakenClient`'s typed endpoint methods.
+enum TypedResponse {
+    Time(answer::Answer<answer::TimeResult>),
+    Ticker(answer::Answer<answer::TickerResult>),
+    Orders(answer::Answer<answer::OrdersResult>),
+}
 
-pub trait Validatable {
-    fn check_valid(&self);
+/// What `i_request` got back: a known endpoint goes through a typed
+/// `KrakenClient` method, anything else falls back to the raw response so
+/// status-only scenarios still work against arbitrary urls.
+enum StepResponse {
+    Typed(TypedResponse),
+    Raw(ApiResponse),
+}
+
+/// Which websocket channel a `given` step has subscribed to, resolved lazily
+/// by the `when("I receive ... updates")` step.
+enum WsSubscription {
+    Ticker { pairs: Vec<String> },
+    OpenOrders { token: String },
 }
 
 #[derive(WorldInit)]
 pub struct MyWorld {
-    request_builder: Option<RequestBuilder>,
-    response: Option<Response>,
-    api_public_key: String,
-    api_private_key: String,
-    otp_setup_key: String,
+    client: KrakenClient,
+    pending_request: Option<PendingRequest>,
+    response: Option<StepResponse>,
+    ws_subscription: Option<WsSubscription>,
+    ws_messages: Vec<serde_json::Value>,
 }
 
 #[async_trait(?Send)]
 impl World for MyWorld {
-    type Error = Infallible;
+    type Error = KrakenError;
 
-    async fn new() -> Result<Self, Infallible> {
+    async fn new() -> Result<Self, KrakenError> {
         use std::env;
+        fn required_env(name: &str) -> Result<String, KrakenError> {
+            env::var(name)
+                .map_err(|_| KrakenError::Validation(format!("missing environment variable {}", name)))
+        }
+        let otp = OtpSource::TotpSecret(required_env("OTP_Setup_Key")?);
         Ok(Self {
-            request_builder: None,
+            client: KrakenClient::new(
+                required_env("API_Public_Key")?,
+                required_env("API_Private_Key")?,
+                otp,
+            )?,
+            pending_request: None,
             response: None,
-            api_public_key: env::var("API_Public_Key")
-                .expect("to have the environment variable API_Public_Key"),
-            api_private_key: env::var("API_Private_Key")
-                .expect("to have the environment variable API_Private_Key"),
-            otp_setup_key: env::var("OTP_Setup_Key")
-                .expect("to have the environment variable OTP_Setup_Key"),
+            ws_subscription: None,
+            ws_messages: Vec::new(),
         })
     }
 }
 
 #[given(regex = "A request to public url (.*)")]
 fn public_api(world: &mut MyWorld, url: String) {
-    let request_url = format!("{}{}", API_DOMAIN, url);
-    let req_builder = Client::new()
-        .get(request_url)
-        .header("User-Agent", "Kraken REST API");
-    world.request_builder = Some(req_builder);
-}
-
-fn otp_token(otp_setup_key: &str) -> String {
-    let start_code =
-        base32::decode(base32::Alphabet::RFC4648 { padding: false }, otp_setup_key).unwrap();
-    let otp_code = oath::totp_raw_now(&start_code, 6, 0, 30, &oath::HashType::SHA1);
-    otp_code.to_string()
+    world.pending_request = Some(PendingRequest::Public { path: url });
 }
 
 #[given(regex = "An authenticated request to private url (.*)")]
 fn private_api(world: &mut MyWorld, url: String) {
-    let nonce: u64 = Utc::now().timestamp_millis() as u64;
-    let request_url = format!("{}{}", API_DOMAIN, url);
-    let otp_code = otp_token(&world.otp_setup_key);
-    let post_data = [("nonce", &nonce.to_string()), ("otp", &otp_code)];
-    let to_hash = format!(
-        "{}{}",
-        nonce,
-        serde_urlencoded::to_string(post_data).expect("to encode post_data")
-    );
-
-    use sha2::{Digest, Sha256, Sha512};
-    let sha256_digest = Sha256::digest(to_hash.as_bytes());
-
-    use hmac::{Hmac, Mac, NewMac};
-    type HmacSha512 = Hmac<Sha512>;
-    let api_secret = base64::decode(world.api_private_key.as_str()).expect("to decode private key");
-    let mut mac = HmacSha512::new_varkey(&api_secret).expect("to be able to create hmac");
-    mac.update(&url.as_bytes());
-    mac.update(&sha256_digest);
-    let hmac_sha512 = mac.finalize();
-
-    let req_builder = Client::new()
-        .post(request_url)
-        .form(&post_data)
-        .header("API-Key", world.api_public_key.clone())
-        .header("API-Sign", base64::encode(hmac_sha512.into_bytes()))
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .header("User-Agent", "Kraken REST API");
-    world.request_builder.replace(req_builder);
+    world.pending_request = Some(PendingRequest::Private { path: url });
+}
+
+#[given(regex = "A subscription to the ticker channel for (.*)")]
+fn ticker_subscription(world: &mut MyWorld, pairs: String) {
+    let pairs = pairs.split(',').map(|p| p.trim().to_string()).collect();
+    world.ws_subscription = Some(WsSubscription::Ticker { pairs });
+}
+
+#[given("A subscription to the openOrders channel")]
+async fn open_orders_subscription(world: &mut MyWorld) {
+    expect_step(open_orders_subscription_impl(world).await);
+}
+
+async fn open_orders_subscription_impl(world: &mut MyWorld) -> Result<(), KrakenError> {
+    let answer = world.client.websockets_token().await?;
+    answer.check_valid()?;
+    world.ws_subscription = Some(WsSubscription::OpenOrders {
+        token: answer.result.unwrap().token,
+    });
+    Ok(())
+}
+
+// cucumber_rust 0.9's codegen discards whatever a step function returns
+// (`#func_name(...).await;`), and its runner only fails a step via
+// `panic::catch_unwind`. So a step that wants a failing scenario has to
+// panic at its own boundary rather than return an `Err` — each `#[given]`/
+// `#[when]`/`#[then]` below keeps its `Result`-returning logic in an `_impl`
+// helper and unwraps it here.
+fn expect_step(result: Result<(), KrakenError>) {
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
 }
 
 #[when("I send it")]
 async fn i_request(world: &mut MyWorld) {
-    let req = world
-        .request_builder
+    expect_step(i_request_impl(world).await);
+}
+
+async fn i_request_impl(world: &mut MyWorld) -> Result<(), KrakenError> {
+    let pending = world
+        .pending_request
         .take()
-        .expect("to have a request already built");
-    let res = req.send().await;
-    if !res.is_ok() {
-        println!("{:?}", res);
-        panic!("Server responded with error")
+        .ok_or_else(|| KrakenError::Validation("no request has been built yet".into()))?;
+    world.response = Some(fetch(&mut world.client, pending).await?);
+    Ok(())
+}
+
+/// Routes known endpoints through `KrakenClient`'s typed methods, so the
+/// steps are thin wrappers around them rather than hand-building the
+/// request/decode logic; anything else falls back to a raw call.
+async fn fetch(client: &mut KrakenClient, pending: PendingRequest) -> Result<StepResponse, KrakenError> {
+    match pending {
+        PendingRequest::Public { path } if path == "/0/public/Time" => Ok(StepResponse::Typed(
+            TypedResponse::Time(client.server_time().await?),
+        )),
+        PendingRequest::Public { path } => match ticker_pairs(&path) {
+            Some(pairs) => Ok(StepResponse::Typed(TypedResponse::Ticker(
+                client.ticker(&pairs).await?,
+            ))),
+            None => Ok(StepResponse::Raw(client.get_raw(&path).await?)),
+        },
+        PendingRequest::Private { path } if path == "/0/private/OpenOrders" => Ok(
+            StepResponse::Typed(TypedResponse::Orders(client.open_orders().await?)),
+        ),
+        PendingRequest::Private { path } => {
+            Ok(StepResponse::Raw(client.post_private_raw(&path, &[]).await?))
+        }
     }
-    world.response = res.ok();
+}
+
+/// Extracts the comma-separated `pair` query parameter from a `/0/public/
+/// Ticker?pair=...` path, the same way `ticker_subscription` splits the
+/// websocket channel's pair list.
+fn ticker_pairs(path: &str) -> Option<Vec<String>> {
+    let query = path.strip_prefix("/0/public/Ticker?")?;
+    let pairs = query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("pair="))?
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .collect();
+    Some(pairs)
 }
 
 #[then(regex = "The server responds with status (.*)")]
 fn server_responds(world: &mut MyWorld, status: String) {
+    expect_step(server_responds_impl(world, status));
+}
+
+fn server_responds_impl(world: &mut MyWorld, status: String) -> Result<(), KrakenError> {
     match status.to_lowercase().as_str() {
-        "ok" => {
-            let status = world.response.as_ref().map(|r| r.status().is_success());
-            if status != Some(true) {
-                println!("{:?}", world.response);
+        "ok" => match world.response.as_ref() {
+            // A typed call already decoded successfully to get here.
+            Some(StepResponse::Typed(_)) => Ok(()),
+            Some(StepResponse::Raw(response)) if response.status.is_success() => Ok(()),
+            other => {
+                let status = match other {
+                    Some(StepResponse::Raw(response)) => Some(response.status),
+                    _ => None,
+                };
+                Err(KrakenError::Validation(format!(
+                    "expected a successful response, got {:?}",
+                    status
+                )))
             }
-            assert_eq!(status, Some(true));
-        }
-        _ => panic!("not implemented"),
+        },
+        _ => Err(KrakenError::Validation(format!(
+            "unrecognized status check: {}",
+            status
+        ))),
     }
 }
 
 #[then(regex = "The response has the correct (time|ticker|orders) format")]
-async fn response_time_format(world: &mut MyWorld, check_type: String) {
-    let response = world.response.take().expect("to have a response");
-    let resp_bytes = response
-        .bytes()
-        .await
-        .expect("to have been able to read response");
-    match check_type.to_lowercase().as_str() {
-        "time" => {
-            //json response validation
-            let response_data: answer_data::Answer<answer_data::TimeResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            response_data.check_valid();
+fn response_time_format(world: &mut MyWorld, check_type: String) {
+    expect_step(response_time_format_impl(world, check_type));
+}
+
+fn response_time_format_impl(world: &mut MyWorld, check_type: String) -> Result<(), KrakenError> {
+    let response = world
+        .response
+        .take()
+        .ok_or_else(|| KrakenError::Validation("no response has been received yet".into()))?;
+    match (check_type.to_lowercase().as_str(), response) {
+        ("time", StepResponse::Typed(TypedResponse::Time(response_data))) => {
+            response_data.check_valid()?;
+            println!(
+                "Server responded with time: {}",
+                response_data.result.unwrap().rfc1123
+            );
+        }
+        ("time", StepResponse::Raw(response)) => {
+            let response_data: answer::Answer<answer::TimeResult> =
+                serde_json::from_slice(&response.body)?;
+            response_data.check_valid()?;
             println!(
                 "Server responded with time: {}",
                 response_data.result.unwrap().rfc1123
             );
         }
-        "ticker" => {
-            //json response validation
-            let response_data: answer_data::Answer<answer_data::TickerResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            response_data.check_valid();
-            response_data.result.unwrap().print_price();
+        ("ticker", StepResponse::Typed(TypedResponse::Ticker(response_data))) => {
+            response_data.check_valid()?;
+            let result = response_data.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
+        }
+        ("ticker", StepResponse::Raw(response)) => {
+            let response_data: answer::Answer<answer::TickerResult> =
+                serde_json::from_slice(&response.body)?;
+            response_data.check_valid()?;
+            let result = response_data.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
         }
-        "orders" => {
-            let response_data: answer_data::Answer<answer_data::OrdersResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            response_data.check_valid();
+        ("orders", StepResponse::Typed(TypedResponse::Orders(response_data))) => {
+            response_data.check_valid()?;
             let result = response_data.result.unwrap(); //can't fail since check_valid would return failure
             let order_names: Vec<&String> = result.open.as_object().unwrap().keys().collect();
             println!("Got {} open orders: {:?}", order_names.len(), order_names);
             println!("Orders_json {}", result.open.to_string());
         }
-        _ => panic!("unrecognized check type"),
+        ("orders", StepResponse::Raw(response)) => {
+            let response_data: answer::Answer<answer::OrdersResult> =
+                serde_json::from_slice(&response.body)?;
+            response_data.check_valid()?;
+            let result = response_data.result.unwrap(); //can't fail since check_valid would return failure
+            let order_names: Vec<&String> = result.open.as_object().unwrap().keys().collect();
+            println!("Got {} open orders: {:?}", order_names.len(), order_names);
+            println!("Orders_json {}", result.open.to_string());
+        }
+        (other, _) => {
+            return Err(KrakenError::Validation(format!(
+                "unrecognized check type: {}",
+                other
+            )))
+        }
+    }
+    Ok(())
+}
+
+#[when(regex = "I receive (\\d+) updates")]
+async fn receive_updates(world: &mut MyWorld, count: String) {
+    expect_step(receive_updates_impl(world, count).await);
+}
+
+async fn receive_updates_impl(world: &mut MyWorld, count: String) -> Result<(), KrakenError> {
+    let count: usize = count
+        .parse()
+        .map_err(|e| KrakenError::Validation(format!("can't parse update count: {}", e)))?;
+    let subscription = world
+        .ws_subscription
+        .as_ref()
+        .ok_or_else(|| KrakenError::Validation("no websocket subscription has been set up".into()))?;
+    let (url, frame) = match subscription {
+        WsSubscription::Ticker { pairs } => (ws::PUBLIC_WS_URL, ws::ticker_subscribe_frame(pairs)),
+        WsSubscription::OpenOrders { token } => {
+            (ws::PRIVATE_WS_URL, ws::open_orders_subscribe_frame(token))
+        }
+    };
+    world.ws_messages = ws::collect_channel_messages(url, frame, count).await?;
+    Ok(())
+}
+
+#[then("The streamed ticker has the correct format")]
+fn streamed_ticker_format(world: &mut MyWorld) {
+    expect_step(streamed_ticker_format_impl(world));
+}
+
+fn streamed_ticker_format_impl(world: &mut MyWorld) -> Result<(), KrakenError> {
+    for message in &world.ws_messages {
+        let fields = message
+            .as_array()
+            .ok_or_else(|| KrakenError::Validation("ticker update is not a JSON array".into()))?;
+        if fields.len() != 4 {
+            return Err(KrakenError::Validation(format!(
+                "expected a 4-element ticker update, got {} fields",
+                fields.len()
+            )));
+        }
+        let data: answer::TickerResultData = serde_json::from_value(fields[1].clone())?;
+        data.check_valid()?;
     }
+    Ok(())
 }
 
 #[tokio::main]
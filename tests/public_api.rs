@@ -1,27 +1,39 @@
 use cucumber_rust::{async_trait, given, then, when, World, WorldInit};
-use reqwest::{Client, Response};
+use open_orders::answer::{self, Validatable};
+use open_orders::{ApiResponse, KrakenClient, KrakenError, OtpSource};
 use serde_json;
-use std::convert::Infallible;
 
-const API_DOMAIN: &str = "https://api.kraken.com";
+/// A decoded answer from one of `KrakenClient`'s typed endpoint methods.
+enum TypedResponse {
+    Time(answer::Answer<answer::TimeResult>),
+    Ticker(answer::Answer<answer::TickerResult>),
+}
 
-pub trait Validatable {
-    fn check_valid(&self);
+/// What `i_request` got back: a known endpoint goes through a typed
+/// `KrakenClient` method, anything else falls back to the raw response so
+/// status-only scenarios still work against arbitrary urls.
+enum StepResponse {
+    Typed(TypedResponse),
+    Raw(ApiResponse),
 }
 
 #[derive(WorldInit)]
 pub struct MyWorld {
-    request_url: String,
-    response: Option<Response>,
+    client: KrakenClient,
+    request_path: String,
+    response: Option<StepResponse>,
 }
 
 #[async_trait(?Send)]
 impl World for MyWorld {
-    type Error = Infallible;
+    type Error = KrakenError;
 
-    async fn new() -> Result<Self, Infallible> {
+    async fn new() -> Result<Self, KrakenError> {
         Ok(Self {
-            request_url: "".into(),
+            // Only public endpoints are exercised here, so the client never
+            // needs real credentials.
+            client: KrakenClient::new(String::new(), String::new(), OtpSource::None)?,
+            request_path: "".into(),
             response: None,
         })
     }
@@ -29,179 +41,142 @@ impl World for MyWorld {
 
 #[given(regex = "The api url (.*)")]
 fn the_api(world: &mut MyWorld, url: String) {
-    world.request_url = format!("{}{}", API_DOMAIN, url);
+    world.request_path = url;
+}
+
+// cucumber_rust 0.9's codegen discards whatever a step function returns
+// (`#func_name(...).await;`), and its runner only fails a step via
+// `panic::catch_unwind`. So a step that wants a failing scenario has to
+// panic at its own boundary rather than return an `Err` — each `#[given]`/
+// `#[when]`/`#[then]` below keeps its `Result`-returning logic in an `_impl`
+// helper and unwraps it here.
+fn expect_step(result: Result<(), KrakenError>) {
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
 }
 
 #[when("I do a GET request to it")]
 async fn i_request(world: &mut MyWorld) {
-    let res = Client::new()
-        .get(world.request_url.as_str())
-        .header("User-Agent", "Kraken REST API")
-        .send()
-        .await;
-    assert!(res.is_ok());
-    world.response = res.ok();
+    expect_step(i_request_impl(world).await);
 }
 
-#[then(regex = "The server responds with status (.*)")]
-fn server_responds(world: &mut MyWorld, status: String) {
-    match status.to_lowercase().as_str() {
-        "ok" => {
-            let status = world.response.as_ref().map(|r| r.status().is_success());
-            if status != Some(true) {
-                println!("{:?}", world.response);
-            }
-            assert_eq!(status, Some(true));
-        }
-        _ => panic!("not implemented"),
-    }
+async fn i_request_impl(world: &mut MyWorld) -> Result<(), KrakenError> {
+    world.response = Some(fetch(&mut world.client, &world.request_path).await?);
+    Ok(())
 }
 
-mod api_answer {
-    use crate::Validatable;
-    use chrono::DateTime;
-    use serde::Deserialize;
-    use std::collections::HashMap;
-
-    #[derive(Deserialize, Debug)]
-    pub struct TimeResult {
-        pub unixtime: i64,
-        pub rfc1123: String,
+/// Routes known public endpoints through `KrakenClient`'s typed methods, so
+/// the steps are thin wrappers around them rather than hand-building the
+/// request/decode logic; anything else falls back to a raw call.
+async fn fetch(client: &mut KrakenClient, path: &str) -> Result<StepResponse, KrakenError> {
+    if path == "/0/public/Time" {
+        return Ok(StepResponse::Typed(TypedResponse::Time(
+            client.server_time().await?,
+        )));
     }
-
-    impl Validatable for TimeResult {
-        fn check_valid(&self) {
-            // rfc2822 is a newer format of rfc1233, thus they should be compatible
-            let time_rfc2822 = DateTime::parse_from_rfc2822(&self.rfc1123)
-                .expect("to be able to parse rfc1233 time");
-            // Expect that unixtime is the same time as the rfc1233 field
-            assert_eq!(time_rfc2822.timestamp(), self.unixtime);
-        }
+    if let Some(pairs) = ticker_pairs(path) {
+        return Ok(StepResponse::Typed(TypedResponse::Ticker(
+            client.ticker(&pairs).await?,
+        )));
     }
+    Ok(StepResponse::Raw(client.get_raw(path).await?))
+}
 
-    #[derive(Deserialize, Debug)]
-    pub struct TickerResultData {
-        #[serde(rename(deserialize = "a"))]
-        ask: [String; 3],
-
-        #[serde(rename(deserialize = "b"))]
-        bid: [String; 3],
-
-        #[serde(rename(deserialize = "c"))]
-        closed: [String; 2],
-
-        #[serde(rename(deserialize = "v"))]
-        volume: [String; 2],
-
-        #[serde(rename(deserialize = "p"))]
-        weighted_average_volume: [String; 2],
-
-        #[serde(rename(deserialize = "t"))]
-        number_of_trades: [u64; 2],
+/// Extracts the comma-separated `pair` query parameter from a `/0/public/
+/// Ticker?pair=...` path, the same way `ticker_subscription` in
+/// `tests/api_tests.rs` splits the websocket channel's pair list.
+fn ticker_pairs(path: &str) -> Option<Vec<String>> {
+    let query = path.strip_prefix("/0/public/Ticker?")?;
+    let pairs = query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("pair="))?
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .collect();
+    Some(pairs)
+}
 
-        #[serde(rename(deserialize = "l"))]
-        low: [String; 2],
-        #[serde(rename(deserialize = "h"))]
-        high: [String; 2],
-        #[serde(rename(deserialize = "o"))]
-        day_opening_price: String,
-    }
+#[then(regex = "The server responds with status (.*)")]
+fn server_responds(world: &mut MyWorld, status: String) {
+    expect_step(server_responds_impl(world, status));
+}
 
-    // Check if the array is parsable as float (decimal would be better, buf float is also ok here)
-    fn as_float_array(arr: &[String]) -> Vec<f64> {
-        use std::str::FromStr;
-        let vals: Result<Vec<f64>, std::num::ParseFloatError> =
-            arr.iter().map(|val| f64::from_str(val)).collect();
-        vals.expect("to be able to parse all values")
+fn server_responds_impl(world: &mut MyWorld, status: String) -> Result<(), KrakenError> {
+    match status.to_lowercase().as_str() {
+        "ok" => match world.response.as_ref() {
+            // A typed call already decoded successfully to get here.
+            Some(StepResponse::Typed(_)) => Ok(()),
+            Some(StepResponse::Raw(response)) if response.status.is_success() => Ok(()),
+            other => {
+                let status = match other {
+                    Some(StepResponse::Raw(response)) => Some(response.status),
+                    _ => None,
+                };
+                Err(KrakenError::Validation(format!(
+                    "expected a successful response, got {:?}",
+                    status
+                )))
+            }
+        },
+        _ => Err(KrakenError::Validation(format!(
+            "unrecognized status check: {}",
+            status
+        ))),
     }
+}
 
-    impl Validatable for TickerResultData {
-        fn check_valid(&self) {
-            assert_ne!(self.number_of_trades[0], 0);
-            assert_ne!(self.number_of_trades[1], 0);
-            assert!(self.number_of_trades[0] < self.number_of_trades[1]);
-            let asks = as_float_array(self.ask.as_ref());
-            assert!(asks.iter().all(|&v| v > 0.0));
-
-            let bids = as_float_array(self.bid.as_ref());
-            assert!(bids.iter().all(|&v| v > 0.0));
-
-            let closed = as_float_array(self.closed.as_ref());
-            //maybe this fails at beginning of the day
-            assert!(closed.iter().all(|&v| v > 0.0));
-
-            let volume = as_float_array(self.volume.as_ref());
-            // since we only test with XBT, the volume for last 24 hours can't be null
-            // at beginning of the day this can be null
-            assert!(volume[1..].iter().all(|&v| v > 0.0));
-
-            let wav = as_float_array(self.weighted_average_volume.as_ref());
-            // since we only test with XBT, the volume for last 24 hours can't be null
-            // at beginning of the day this can be null
-            assert!(wav[1..].iter().all(|&v| v > 0.0));
-
-            let low = as_float_array(self.low.as_ref());
-            assert!(low.iter().all(|&v| v > 0.0));
-
-            let high = as_float_array(self.high.as_ref());
-            assert!(high.iter().all(|&v| v > 0.0));
-
-            let open = as_float_array(&[self.day_opening_price.clone()][..]);
-            assert!(open.iter().all(|&v| v > 0.0));
-        }
-    }
+#[then(regex = "The response has the correct (time|ticker) format")]
+fn response_time_format(world: &mut MyWorld, check_type: String) {
+    expect_step(response_time_format_impl(world, check_type));
+}
 
-    #[derive(Deserialize, Debug)]
-    pub struct TickerResult(HashMap<String, TickerResultData>);
-    impl TickerResult {
-        pub fn print_price(&self) {
-            println!("XBT/USD last price: {}", self.0["XXBTZUSD"].closed[0]);
+fn response_time_format_impl(world: &mut MyWorld, check_type: String) -> Result<(), KrakenError> {
+    let response = world
+        .response
+        .take()
+        .ok_or_else(|| KrakenError::Validation("no response has been received yet".into()))?;
+    match (check_type.to_lowercase().as_str(), response) {
+        ("time", StepResponse::Typed(TypedResponse::Time(resp_json))) => {
+            resp_json.check_valid()?;
+            println!(
+                "Server responded with time: {}",
+                resp_json.result.unwrap().rfc1123
+            );
         }
-    }
-
-    impl Validatable for TickerResult {
-        fn check_valid(&self) {
-            let ticker_names: Vec<&str> = self.0.keys().map(|s| s.as_str()).collect();
-            assert_eq!(ticker_names, vec!["XXBTZUSD"]);
-            self.0[ticker_names[0]].check_valid();
+        ("time", StepResponse::Raw(response)) => {
+            let resp_json: answer::Answer<answer::TimeResult> =
+                serde_json::from_slice(&response.body)?;
+            resp_json.check_valid()?;
+            println!(
+                "Server responded with time: {}",
+                resp_json.result.unwrap().rfc1123
+            );
         }
-    }
-    #[derive(Deserialize, Debug)]
-    pub struct Answer<T> {
-        pub error: Vec<String>,
-        pub result: T,
-    }
-    impl<T: Validatable> Validatable for Answer<T> {
-        fn check_valid(&self) {
-            assert_eq!(self.error.len(), 0);
-            self.result.check_valid();
+        ("ticker", StepResponse::Typed(TypedResponse::Ticker(resp_json))) => {
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
         }
-    }
-}
-#[then(regex = "The response has the correct (time|ticker) format")]
-async fn response_time_format(world: &mut MyWorld, check_type: String) {
-    let response = world.response.take().expect("to have a response");
-    let resp_bytes = response
-        .bytes()
-        .await
-        .expect("to have been able to read response");
-    match check_type.to_lowercase().as_str() {
-        "time" => {
-            //json response validation
-            let resp_json: api_answer::Answer<api_answer::TimeResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            println!("Server responded with time: {}", resp_json.result.rfc1123);
-            resp_json.result.check_valid();
+        ("ticker", StepResponse::Raw(response)) => {
+            let resp_json: answer::Answer<answer::TickerResult> =
+                serde_json::from_slice(&response.body)?;
+            resp_json.check_valid()?;
+            let result = resp_json.result.unwrap();
+            for pair in result.pairs() {
+                result.print_price(pair)?;
+            }
         }
-        "ticker" => {
-            //json response validation
-            let resp_json: api_answer::Answer<api_answer::TickerResult> =
-                serde_json::from_slice(&resp_bytes).expect("to be able to parse response");
-            resp_json.result.check_valid();
-            resp_json.result.print_price();
+        (other, _) => {
+            return Err(KrakenError::Validation(format!(
+                "unrecognized check type: {}",
+                other
+            )))
         }
-        _ => panic!("unrecognized check type"),
     }
+    Ok(())
 }
 
 #[tokio::main]